@@ -1,3 +1,6 @@
+use encoding_rs::Encoding;
+use flate2::write::GzEncoder;
+use flate2::Compression as GzCompression;
 use nu_engine::CallExt;
 use nu_protocol::ast::Call;
 use nu_protocol::engine::{Command, EngineState, Stack};
@@ -7,7 +10,8 @@ use nu_protocol::{
 };
 use std::fs::File;
 use std::io::{BufWriter, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use crate::progress_bar::{self, NuProgressBar};
 
@@ -50,6 +54,40 @@ impl Command for Save {
             .switch("append", "append input to the end of the file", Some('a'))
             .switch("force", "overwrite the destination", Some('f'))
             .switch("progress", "enable progress bar", Some('p'))
+            .switch(
+                "mkdir",
+                "create the destination's (and, with --stderr, that path's) parent directories if they don't already exist",
+                Some('m'),
+            )
+            .switch(
+                "atomic",
+                "write to a temporary file in the destination's directory and rename it into place, so a failed write never corrupts an existing file",
+                None,
+            )
+            .named(
+                "compress",
+                SyntaxShape::String,
+                "compress the output as it's written, one of: gzip, zstd (defaults to the filename's .gz/.zst extension)",
+                None,
+            )
+            .named(
+                "compress-level",
+                SyntaxShape::Int,
+                "compression level to use with --compress (algorithm-specific default if omitted)",
+                None,
+            )
+            .named(
+                "dedup-store",
+                SyntaxShape::Filepath,
+                "content-define-chunk the input into <dir>, storing only chunks not already present and writing a manifest of chunk digests for the destination",
+                None,
+            )
+            .named(
+                "encoding",
+                SyntaxShape::String,
+                "transcode string output to this text encoding before writing, eg utf-16le, latin1, shift_jis (defaults to utf-8)",
+                None,
+            )
             .category(Category::FileSystem)
     }
 
@@ -64,6 +102,8 @@ impl Command for Save {
         let append = call.has_flag("append");
         let force = call.has_flag("force");
         let progress = call.has_flag("progress");
+        let mkdir = call.has_flag("mkdir");
+        let atomic = call.has_flag("atomic") && !append;
 
         let span = call.head;
 
@@ -71,6 +111,29 @@ impl Command for Save {
         let arg_span = path.span;
         let path = Path::new(&path.item);
 
+        if let Some(dedup_dir) = call.get_flag::<Spanned<String>>(engine_state, stack, "dedup-store")? {
+            if let Some(compress) = call.get_flag::<Spanned<String>>(engine_state, stack, "compress")? {
+                return Err(ShellError::GenericError(
+                    "Incompatible flags".into(),
+                    "--compress has no effect with --dedup-store; chunks are always stored uncompressed".into(),
+                    Some(compress.span),
+                    None,
+                    Vec::new(),
+                ));
+            }
+            if let Some(encoding) = call.get_flag::<Spanned<String>>(engine_state, stack, "encoding")? {
+                return Err(ShellError::GenericError(
+                    "Incompatible flags".into(),
+                    "--encoding has no effect with --dedup-store; chunks are always stored as raw bytes".into(),
+                    Some(encoding.span),
+                    None,
+                    Vec::new(),
+                ));
+            }
+
+            return save_to_dedup_store(Path::new(&dedup_dir.item), path, input, span, arg_span, force);
+        }
+
         let path_exists = path.exists();
         if path_exists && !force && !append {
             return Err(ShellError::GenericError(
@@ -85,15 +148,25 @@ impl Command for Save {
             ));
         }
 
+        if mkdir {
+            create_parent_dir(path)?;
+        }
+
+        // When `--atomic` is requested we never touch `path` directly: we write to a sibling
+        // temporary file and only rename it over the destination once the write has fully
+        // succeeded, so a process that dies mid-write can't leave a truncated file behind.
+        let tmp_path = atomic.then(|| atomic_temp_path(path));
+        let write_target = tmp_path.as_deref().unwrap_or(path);
+
         let file = match (append, path_exists) {
             (true, true) => std::fs::OpenOptions::new()
                 .write(true)
                 .append(true)
-                .open(path),
-            _ => std::fs::File::create(path),
+                .open(write_target),
+            _ => std::fs::File::create(write_target),
         };
 
-        let mut file = match file {
+        let file = match file {
             Ok(file) => file,
             Err(err) => {
                 return Err(ShellError::GenericError(
@@ -114,6 +187,9 @@ impl Command for Save {
                 if stderr_path == path {
                     Some(file.try_clone()?)
                 } else {
+                    if mkdir {
+                        create_parent_dir(stderr_path)?;
+                    }
                     match std::fs::File::create(stderr_path) {
                         Ok(file) => Some(file),
                         Err(err) => {
@@ -130,6 +206,33 @@ impl Command for Save {
             }
         };
 
+        let compress_flag = call.get_flag::<Spanned<String>>(engine_state, stack, "compress")?;
+        let compress_level = call
+            .get_flag::<Spanned<i64>>(engine_state, stack, "compress-level")?
+            .map(|level| level.item);
+        let compress_algo = match &compress_flag {
+            Some(flag) => Some(CompressAlgo::from_name(&flag.item).ok_or_else(|| {
+                ShellError::GenericError(
+                    "Invalid compression algorithm".into(),
+                    format!(
+                        "'{}' is not a recognized --compress algorithm, expected gzip or zstd",
+                        flag.item
+                    ),
+                    Some(flag.span),
+                    None,
+                    Vec::new(),
+                )
+            })?),
+            None if raw => None,
+            None => CompressAlgo::from_extension(path),
+        };
+        let writer = CompressWriter::new(file, compress_algo, compress_level)?;
+
+        let encoding = call
+            .get_flag::<Spanned<String>>(engine_state, stack, "encoding")?
+            .map(|label| resolve_encoding(&label))
+            .transpose()?;
+
         let ext = if raw {
             None
         // if is extern stream , in other words , not value
@@ -142,7 +245,7 @@ impl Command for Save {
                 .map(|name| name.to_string_lossy().to_string())
         };
 
-        if let Some(ext) = ext {
+        let result = if let Some(ext) = ext {
             let output = match engine_state.find_decl(format!("to {}", ext).as_bytes(), &[]) {
                 Some(converter_id) => {
                     let output = engine_state.get_decl(converter_id).run(
@@ -159,23 +262,9 @@ impl Command for Save {
 
             match output {
                 Value::String { val, .. } => {
-                    if let Err(err) = file.write_all(val.as_bytes()) {
-                        return Err(ShellError::IOError(err.to_string()));
-                    } else {
-                        file.flush()?
-                    }
-
-                    Ok(PipelineData::empty())
-                }
-                Value::Binary { val, .. } => {
-                    if let Err(err) = file.write_all(&val) {
-                        return Err(ShellError::IOError(err.to_string()));
-                    } else {
-                        file.flush()?
-                    }
-
-                    Ok(PipelineData::empty())
+                    write_and_flush(writer, &encode_text(&val, encoding, span)?)
                 }
+                Value::Binary { val, .. } => write_and_flush(writer, &val),
                 Value::List { vals, .. } => {
                     let val = vals
                         .into_iter()
@@ -184,13 +273,7 @@ impl Command for Save {
                         .join("\n")
                         + "\n";
 
-                    if let Err(err) = file.write_all(val.as_bytes()) {
-                        return Err(ShellError::IOError(err.to_string()));
-                    } else {
-                        file.flush()?
-                    }
-
-                    Ok(PipelineData::empty())
+                    write_and_flush(writer, &encode_text(&val, encoding, span)?)
                 }
                 // Propagate errors by explicitly matching them before the final case.
                 Value::Error { error } => Err(error),
@@ -213,7 +296,7 @@ impl Command for Save {
                     // delegate a thread to redirect stderr to result.
                     let handler = stderr.map(|stderr_stream| match stderr_file {
                         Some(stderr_file) => std::thread::spawn(move || {
-                            stream_to_file(stderr_stream, stderr_file, span, progress)
+                            stream_to_file(stderr_stream, stderr_file, span, progress, encoding)
                         }),
                         None => std::thread::spawn(move || {
                             let _ = stderr_stream.into_bytes();
@@ -221,42 +304,26 @@ impl Command for Save {
                         }),
                     });
 
-                    let res = stream_to_file(stream, file, span, progress);
+                    let res = stream_to_file(stream, writer, span, progress, encoding);
                     if let Some(h) = handler {
                         match h.join() {
-                            Err(err) => {
-                                return Err(ShellError::ExternalCommand(
-                                    "Fail to receive external commands stderr message".to_string(),
-                                    format!("{err:?}"),
-                                    span,
-                                ))
-                            }
-                            Ok(res) => res,
-                        }?;
-                        res
+                            Err(err) => Err(ShellError::ExternalCommand(
+                                "Fail to receive external commands stderr message".to_string(),
+                                format!("{err:?}"),
+                                span,
+                            )),
+                            Ok(Err(err)) => Err(err),
+                            Ok(Ok(_)) => res,
+                        }
                     } else {
                         res
                     }
                 }
                 input => match input.into_value(span) {
                     Value::String { val, .. } => {
-                        if let Err(err) = file.write_all(val.as_bytes()) {
-                            return Err(ShellError::IOError(err.to_string()));
-                        } else {
-                            file.flush()?
-                        }
-
-                        Ok(PipelineData::empty())
-                    }
-                    Value::Binary { val, .. } => {
-                        if let Err(err) = file.write_all(&val) {
-                            return Err(ShellError::IOError(err.to_string()));
-                        } else {
-                            file.flush()?
-                        }
-
-                        Ok(PipelineData::empty())
+                        write_and_flush(writer, &encode_text(&val, encoding, span)?)
                     }
+                    Value::Binary { val, .. } => write_and_flush(writer, &val),
                     Value::List { vals, .. } => {
                         let val = vals
                             .into_iter()
@@ -265,13 +332,7 @@ impl Command for Save {
                             .join("\n")
                             + "\n";
 
-                        if let Err(err) = file.write_all(val.as_bytes()) {
-                            return Err(ShellError::IOError(err.to_string()));
-                        } else {
-                            file.flush()?
-                        }
-
-                        Ok(PipelineData::empty())
+                        write_and_flush(writer, &encode_text(&val, encoding, span)?)
                     }
                     // Propagate errors by explicitly matching them before the final case.
                     Value::Error { error } => Err(error),
@@ -284,7 +345,9 @@ impl Command for Save {
                     )),
                 },
             }
-        }
+        };
+
+        finalize_atomic_save(tmp_path, path, arg_span, result)
     }
 
     fn examples(&self) -> Vec<Example> {
@@ -314,18 +377,272 @@ impl Command for Save {
                 example: r#"do -i {} | save foo.txt --stderr bar.txt"#,
                 result: None,
             },
+            Example {
+                description: "Write foo.txt atomically, leaving the previous file untouched if the save fails",
+                example: r#"$large_data | save foo.txt --atomic --force"#,
+                result: None,
+            },
+            Example {
+                description: "Stream-compress a large log as it's saved",
+                example: r#"open --raw big.log | save big.log.zst"#,
+                result: None,
+            },
+            Example {
+                description: "Content-define-chunk and deduplicate a backup against previous versions",
+                example: r#"open --raw backup.tar | save backup.tar --dedup-store ./chunks"#,
+                result: None,
+            },
+            Example {
+                description: "Re-save a string using a legacy text encoding",
+                example: r#"$legacy_text | save out.txt --encoding shift_jis"#,
+                result: None,
+            },
+            Example {
+                description: "Save into a directory tree that doesn't exist yet",
+                example: r#"data | save logs/2024/run.json --mkdir"#,
+                result: None,
+            },
         ]
     }
 }
 
-fn stream_to_file(
+/// Creates `path`'s parent directory tree if it doesn't already exist. Only the directories are
+/// created here; the destination file itself is still created later by the normal open/create
+/// logic, so the existing "destination exists" guard is unaffected.
+fn create_parent_dir(path: &Path) -> Result<(), ShellError> {
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Build a sibling temporary file path for `path`, living in the same directory so that the
+/// final `rename` is guaranteed to be atomic (same filesystem).
+fn atomic_temp_path(path: &Path) -> PathBuf {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "nu-save".to_string());
+
+    let unique = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let tmp_name = format!(".{file_name}.{}.{unique}.tmp", std::process::id());
+
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.join(tmp_name),
+        _ => PathBuf::from(tmp_name),
+    }
+}
+
+/// If we wrote to a temporary file, either rename it over the destination on success or remove
+/// it on failure, leaving the original file (if any) untouched either way.
+fn finalize_atomic_save(
+    tmp_path: Option<PathBuf>,
+    path: &Path,
+    arg_span: Span,
+    result: Result<PipelineData, ShellError>,
+) -> Result<PipelineData, ShellError> {
+    let Some(tmp_path) = tmp_path else {
+        return result;
+    };
+
+    match result {
+        Ok(value) => match std::fs::rename(&tmp_path, path) {
+            Ok(()) => Ok(value),
+            Err(err) => {
+                let _ = std::fs::remove_file(&tmp_path);
+                Err(ShellError::GenericError(
+                    "Failed to finalize atomic save".into(),
+                    err.to_string(),
+                    Some(arg_span),
+                    None,
+                    Vec::new(),
+                ))
+            }
+        },
+        Err(err) => {
+            let _ = std::fs::remove_file(&tmp_path);
+            Err(err)
+        }
+    }
+}
+
+/// The compression algorithms `--compress` understands.
+#[derive(Clone, Copy)]
+enum CompressAlgo {
+    Gzip,
+    Zstd,
+}
+
+impl CompressAlgo {
+    fn from_name(name: &str) -> Option<CompressAlgo> {
+        match name {
+            "gzip" | "gz" => Some(CompressAlgo::Gzip),
+            "zstd" | "zst" => Some(CompressAlgo::Zstd),
+            _ => None,
+        }
+    }
+
+    fn from_extension(path: &Path) -> Option<CompressAlgo> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Some(CompressAlgo::Gzip),
+            Some("zst") => Some(CompressAlgo::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// Wraps the destination file so bytes are compressed as they're written, without ever
+/// buffering the whole payload in memory. Falls back to a plain buffered writer when no
+/// compression was requested.
+enum CompressWriter {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<File>),
+    Zstd(zstd::stream::Encoder<'static, File>),
+}
+
+impl CompressWriter {
+    fn new(
+        file: File,
+        algo: Option<CompressAlgo>,
+        level: Option<i64>,
+    ) -> Result<CompressWriter, ShellError> {
+        match algo {
+            None => Ok(CompressWriter::Plain(BufWriter::new(file))),
+            Some(CompressAlgo::Gzip) => {
+                let level = level.map(|level| level.clamp(0, 9) as u32).unwrap_or(6);
+                Ok(CompressWriter::Gzip(GzEncoder::new(
+                    file,
+                    GzCompression::new(level),
+                )))
+            }
+            Some(CompressAlgo::Zstd) => {
+                let level = level.map(|level| level as i32).unwrap_or(0);
+                Ok(CompressWriter::Zstd(zstd::stream::Encoder::new(
+                    file, level,
+                )?))
+            }
+        }
+    }
+
+    /// Flush any buffered bytes and, for a compressed stream, write its final footer.
+    fn finish(self) -> std::io::Result<()> {
+        match self {
+            CompressWriter::Plain(mut writer) => writer.flush(),
+            CompressWriter::Gzip(encoder) => encoder.finish().map(|_| ()),
+            CompressWriter::Zstd(encoder) => encoder.finish().map(|_| ()),
+        }
+    }
+}
+
+impl Write for CompressWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            CompressWriter::Plain(writer) => writer.write(buf),
+            CompressWriter::Gzip(writer) => writer.write(buf),
+            CompressWriter::Zstd(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            CompressWriter::Plain(writer) => writer.flush(),
+            CompressWriter::Gzip(writer) => writer.flush(),
+            CompressWriter::Zstd(writer) => writer.flush(),
+        }
+    }
+}
+
+/// A `Write` destination that needs an explicit finalization step once every byte has been
+/// written: a compressed stream must emit its epilogue (zstd frame footer, gzip CRC/size
+/// trailer), and the `stream_to_file` destination is generic over both `CompressWriter` and a
+/// plain `File` (the stderr redirection path), so both need to implement this the same way
+/// `write_and_flush` already finishes a `CompressWriter` for the buffered-value path.
+trait FinishableWrite: Write {
+    fn finish_write(self) -> std::io::Result<()>;
+}
+
+impl FinishableWrite for File {
+    fn finish_write(mut self) -> std::io::Result<()> {
+        self.flush()
+    }
+}
+
+impl FinishableWrite for CompressWriter {
+    fn finish_write(self) -> std::io::Result<()> {
+        self.finish()
+    }
+}
+
+fn resolve_encoding(label: &Spanned<String>) -> Result<&'static Encoding, ShellError> {
+    Encoding::for_label(label.item.as_bytes()).ok_or_else(|| {
+        ShellError::GenericError(
+            "Unknown encoding".into(),
+            format!("'{}' is not a known text encoding", label.item),
+            Some(label.span),
+            Some("see https://encoding.spec.whatwg.org/#names-and-labels for valid labels".into()),
+            Vec::new(),
+        )
+    })
+}
+
+/// Transcodes `val` to `encoding` (UTF-8 passthrough when `None`), erroring out instead of
+/// silently mangling characters the target encoding can't represent.
+fn encode_text(
+    val: &str,
+    encoding: Option<&'static Encoding>,
+    span: Span,
+) -> Result<Vec<u8>, ShellError> {
+    let Some(encoding) = encoding else {
+        return Ok(val.as_bytes().to_vec());
+    };
+
+    // `Encoding::encode` writes UTF-8 for the UTF-16 encodings, so build those bytes directly.
+    if encoding == encoding_rs::UTF_16LE || encoding == encoding_rs::UTF_16BE {
+        let little_endian = encoding == encoding_rs::UTF_16LE;
+        return Ok(val
+            .encode_utf16()
+            .flat_map(|unit| if little_endian { unit.to_le_bytes() } else { unit.to_be_bytes() })
+            .collect());
+    }
+
+    let (encoded, _, had_unmappable_chars) = encoding.encode(val);
+    if had_unmappable_chars {
+        return Err(ShellError::GenericError(
+            "Unmappable character".into(),
+            format!(
+                "the output contains characters that cannot be represented in {}",
+                encoding.name()
+            ),
+            Some(span),
+            None,
+            Vec::new(),
+        ));
+    }
+
+    Ok(encoded.into_owned())
+}
+
+fn write_and_flush(mut writer: CompressWriter, bytes: &[u8]) -> Result<PipelineData, ShellError> {
+    if let Err(err) = writer.write_all(bytes) {
+        return Err(ShellError::IOError(err.to_string()));
+    }
+    writer.finish()?;
+
+    Ok(PipelineData::empty())
+}
+
+fn stream_to_file<W: FinishableWrite>(
     mut stream: RawStream,
-    file: File,
+    mut writer: W,
     span: Span,
     progress: bool,
+    encoding: Option<&'static Encoding>,
 ) -> Result<PipelineData, ShellError> {
-    let mut writer = BufWriter::new(file);
-
     let mut bytes_processed: u64 = 0;
     let bytes_processed_p = &mut bytes_processed;
     let file_total_size = stream.known_size;
@@ -344,11 +661,15 @@ fn stream_to_file(
         (None, None)
     };
 
+    // Borrow `writer` rather than moving it into the closure so it's still ours to call
+    // `finish_write` on once every chunk has been written.
+    let writer_ref = &mut writer;
+
     let result = stream
         .try_for_each(move |result| {
             let buf = match result {
                 Ok(v) => match v {
-                    Value::String { val, .. } => val.into_bytes(),
+                    Value::String { val, .. } => encode_text(&val, encoding, span)?,
                     Value::Binary { val, .. } => val,
                     // Propagate errors by explicitly matching them before the final case.
                     Value::Error { error } => return Err(error),
@@ -372,18 +693,33 @@ fn stream_to_file(
             if progress {
                 // Update the total amount of bytes that has been saved and then print the progress bar
                 *bytes_processed_p += buf.len() as u64;
+
+                // `NuProgressBar` only has a byte-count update method in this tree; a
+                // rate/ETA-aware one (`update_bar_with_rate`) would need to be added to
+                // `progress_bar.rs`, which isn't part of this source tree.
                 if let Some(bar) = &mut bar_opt {
                     bar.update_bar(*bytes_processed_p);
                 }
             }
 
-            if let Err(err) = writer.write(&buf) {
+            if let Err(err) = writer_ref.write(&buf) {
                 return Err(ShellError::IOError(err.to_string()));
             }
             Ok(())
         })
         .map(|_| PipelineData::empty());
 
+    // Finalize the writer (flushing a plain file, or emitting a compressed stream's epilogue)
+    // now that every chunk has been written, the same way `write_and_flush` does for the
+    // buffered-value path. Skip it if the stream itself already failed: there's nothing valid to
+    // finalize, and `finalize_atomic_save` discards the temp file on that path anyway.
+    let result = result.and_then(|data| {
+        writer
+            .finish_write()
+            .map(|_| data)
+            .map_err(|err| ShellError::IOError(err.to_string()))
+    });
+
     // If the `progress` flag is set then
     if progress {
         // If the process failed, stop the progress bar with an error message.
@@ -402,3 +738,177 @@ fn stream_to_file(
     // And finally return the stream result.
     result
 }
+
+/// Past `MIN_CHUNK_SIZE`, each byte ends a chunk with probability `2^-GEAR_MASK_BITS`, so chunks
+/// average roughly `MIN_CHUNK_SIZE + 2^GEAR_MASK_BITS` bytes.
+const GEAR_MASK_BITS: u32 = 13;
+const MIN_CHUNK_SIZE: usize = 2 * 1024;
+const MAX_CHUNK_SIZE: usize = 64 * 1024;
+
+/// A 256-entry table of pseudo-random 64-bit constants used by the gear hash below, generated
+/// once at compile time with a splitmix64-style mix so we don't pull in a `rand` dependency for
+/// a table whose only requirement is "looks random enough to spread chunk boundaries evenly".
+const GEAR_TABLE: [u64; 256] = {
+    let mut table = [0u64; 256];
+    let mut seed: u64 = 0x9E3779B97F4A7C15;
+    let mut i = 0;
+    while i < 256 {
+        seed = seed.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = seed;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+};
+
+/// Splits a byte stream into variable-length, content-defined chunks using a rolling gear hash:
+/// `hash = (hash << 1) + GEAR_TABLE[byte]` over the bytes seen since the last boundary. A
+/// boundary falls wherever the low `GEAR_MASK_BITS` bits of the hash are all zero, which makes
+/// boundaries a function of local content rather than position, so inserting or deleting bytes
+/// only perturbs the chunks touching the edit. `MIN_CHUNK_SIZE`/`MAX_CHUNK_SIZE` keep pathological
+/// inputs (all-zero streams, or streams that never hit a boundary) from producing degenerate
+/// chunks.
+struct ContentDefinedChunker {
+    buf: Vec<u8>,
+    hash: u64,
+}
+
+impl ContentDefinedChunker {
+    fn new() -> Self {
+        Self {
+            buf: Vec::new(),
+            hash: 0,
+        }
+    }
+
+    /// Feed more bytes in, returning any chunks that became complete as a result.
+    fn push(&mut self, data: &[u8]) -> Vec<Vec<u8>> {
+        let mut chunks = Vec::new();
+        let boundary_mask = (1u64 << GEAR_MASK_BITS) - 1;
+
+        for &byte in data {
+            self.buf.push(byte);
+            self.hash = (self.hash << 1).wrapping_add(GEAR_TABLE[byte as usize]);
+
+            let at_boundary = self.buf.len() >= MIN_CHUNK_SIZE && self.hash & boundary_mask == 0;
+            if at_boundary || self.buf.len() >= MAX_CHUNK_SIZE {
+                chunks.push(std::mem::take(&mut self.buf));
+                self.hash = 0;
+            }
+        }
+
+        chunks
+    }
+
+    /// The final, possibly short, chunk left over once the stream is exhausted.
+    fn finish(self) -> Option<Vec<u8>> {
+        (!self.buf.is_empty()).then_some(self.buf)
+    }
+}
+
+/// Writes `chunk` to `<dir>/<hex-digest>` unless a chunk with that digest already exists, and
+/// returns the digest and length to be recorded in the manifest.
+fn store_dedup_chunk(dir: &Path, chunk: &[u8]) -> Result<(String, usize), ShellError> {
+    let digest = blake3::hash(chunk).to_hex().to_string();
+    let chunk_path = dir.join(&digest);
+
+    if !chunk_path.exists() {
+        std::fs::write(&chunk_path, chunk)?;
+    }
+
+    Ok((digest, chunk.len()))
+}
+
+/// Implements `save --dedup-store <dir>`: content-defined-chunk the input, write each unique
+/// chunk under `dir` keyed by its digest, and record the ordered list of (digest, length) pairs
+/// in `<dir>/<filename>.manifest` so the destination can later be reassembled. Like every other
+/// destination write in this file, an existing manifest is only overwritten with `--force`.
+fn save_to_dedup_store(
+    dir: &Path,
+    path: &Path,
+    input: PipelineData,
+    span: Span,
+    arg_span: Span,
+    force: bool,
+) -> Result<PipelineData, ShellError> {
+    std::fs::create_dir_all(dir)?;
+
+    let manifest_name = format!(
+        "{}.manifest",
+        path.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "save".to_string())
+    );
+    let manifest_path = dir.join(manifest_name);
+
+    if manifest_path.exists() && !force {
+        return Err(ShellError::GenericError(
+            "Destination file already exists".into(),
+            format!(
+                "Manifest '{}' already exists",
+                manifest_path.to_string_lossy()
+            ),
+            Some(arg_span),
+            Some("you can use -f, --force to force overwriting the destination".into()),
+            Vec::new(),
+        ));
+    }
+
+    let mut chunker = ContentDefinedChunker::new();
+    let mut manifest = String::new();
+
+    let mut feed = |bytes: &[u8]| -> Result<(), ShellError> {
+        for chunk in chunker.push(bytes) {
+            let (digest, len) = store_dedup_chunk(dir, &chunk)?;
+            manifest.push_str(&format!("{digest} {len}\n"));
+        }
+        Ok(())
+    };
+
+    match input {
+        PipelineData::ExternalStream {
+            stdout: Some(stream),
+            ..
+        } => {
+            for value in stream {
+                match value? {
+                    Value::String { val, .. } => feed(val.as_bytes())?,
+                    Value::Binary { val, .. } => feed(&val)?,
+                    other => {
+                        return Err(ShellError::OnlySupportsThisInputType(
+                            "string or binary".into(),
+                            other.get_type().to_string(),
+                            span,
+                            other.expect_span(),
+                        ))
+                    }
+                }
+            }
+        }
+        PipelineData::ExternalStream { stdout: None, .. } => {}
+        input => match input.into_value(span) {
+            Value::String { val, .. } => feed(val.as_bytes())?,
+            Value::Binary { val, .. } => feed(&val)?,
+            Value::Error { error } => return Err(error),
+            other => {
+                return Err(ShellError::OnlySupportsThisInputType(
+                    "string or binary".into(),
+                    other.get_type().to_string(),
+                    span,
+                    other.expect_span(),
+                ))
+            }
+        },
+    }
+
+    if let Some(chunk) = chunker.finish() {
+        let (digest, len) = store_dedup_chunk(dir, &chunk)?;
+        manifest.push_str(&format!("{digest} {len}\n"));
+    }
+
+    std::fs::write(&manifest_path, manifest)?;
+
+    Ok(PipelineData::empty())
+}
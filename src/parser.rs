@@ -2,13 +2,57 @@ use std::str::Utf8Error;
 
 use crate::{
     lex, lite_parse,
-    parser_state::{Type, VarId},
+    parser_state::{DeclId, Type, VarId},
     LiteBlock, LiteCommand, LiteStatement, ParseError, ParserWorkingSet, Span,
 };
 
 pub struct Signature {
     pub name: String,
-    pub mandatory_positional: Vec<SyntaxShape>,
+    pub mandatory_positional: Vec<PositionalArg>,
+    pub switches: Vec<String>,
+    /// The command's parsed body, stored on the decl so a later interpreter can execute it when
+    /// the command is called, instead of parsing the body only to check for errors and throwing
+    /// it away.
+    pub body: Block,
+}
+
+/// A single named, shaped parameter in a `def` signature, eg the `x: int` in `def foo [x: int]`.
+#[derive(Debug, Clone)]
+pub struct PositionalArg {
+    pub name: String,
+    pub shape: SyntaxShape,
+}
+
+impl PositionalArg {
+    /// The `Type` a variable bound to this parameter should carry while parsing the `def` body.
+    fn ty(&self) -> Type {
+        match self.shape {
+            SyntaxShape::Int => Type::Int,
+            SyntaxShape::Number => Type::Int,
+            SyntaxShape::String => Type::String,
+            _ => Type::Unknown,
+        }
+    }
+}
+
+/// Maps a `def` parameter's type annotation (the `int` in `x: int`) to the `SyntaxShape` it
+/// constrains the argument to. An unrecognized annotation yields `None`; `parse_params` reports
+/// it as an error and leaves that parameter's shape as `SyntaxShape::Any`.
+fn type_from_annotation(bytes: &[u8]) -> Option<SyntaxShape> {
+    match bytes {
+        b"any" => Some(SyntaxShape::Any),
+        b"int" => Some(SyntaxShape::Int),
+        b"number" => Some(SyntaxShape::Number),
+        b"string" => Some(SyntaxShape::String),
+        b"path" => Some(SyntaxShape::FilePath),
+        b"glob" => Some(SyntaxShape::GlobPattern),
+        b"block" => Some(SyntaxShape::Block),
+        b"table" => Some(SyntaxShape::Table),
+        b"range" => Some(SyntaxShape::Range),
+        b"filesize" => Some(SyntaxShape::Filesize),
+        b"duration" => Some(SyntaxShape::Duration),
+        _ => None,
+    }
 }
 
 /// The syntactic shapes that values must match to be passed into a command. You can think of this as the type-checking that occurs when you call a function.
@@ -51,10 +95,105 @@ pub enum SyntaxShape {
     MathExpression,
 }
 
+/// A binary operator appearing in a [`MathExpression`](SyntaxShape::MathExpression) or
+/// [`RowCondition`](SyntaxShape::RowCondition).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Plus,
+    Minus,
+    Multiply,
+    Divide,
+    Modulo,
+    Equal,
+    NotEqual,
+    LessThan,
+    GreaterThan,
+    LessThanOrEqual,
+    GreaterThanOrEqual,
+    And,
+    Or,
+}
+
+impl Operator {
+    fn from_bytes(bytes: &[u8]) -> Option<Operator> {
+        match bytes {
+            b"+" => Some(Operator::Plus),
+            b"-" => Some(Operator::Minus),
+            b"*" => Some(Operator::Multiply),
+            b"/" => Some(Operator::Divide),
+            b"mod" => Some(Operator::Modulo),
+            b"==" => Some(Operator::Equal),
+            b"!=" => Some(Operator::NotEqual),
+            b"<" => Some(Operator::LessThan),
+            b">" => Some(Operator::GreaterThan),
+            b"<=" => Some(Operator::LessThanOrEqual),
+            b">=" => Some(Operator::GreaterThanOrEqual),
+            b"&&" | b"and" => Some(Operator::And),
+            b"||" | b"or" => Some(Operator::Or),
+            _ => None,
+        }
+    }
+
+    /// Higher binds tighter. `*`/`/`/`mod` > `+`/`-` > comparisons > `and` > `or`.
+    fn precedence(self) -> u8 {
+        match self {
+            Operator::Multiply | Operator::Divide | Operator::Modulo => 100,
+            Operator::Plus | Operator::Minus => 90,
+            Operator::Equal
+            | Operator::NotEqual
+            | Operator::LessThan
+            | Operator::GreaterThan
+            | Operator::LessThanOrEqual
+            | Operator::GreaterThanOrEqual => 80,
+            Operator::And => 70,
+            Operator::Or => 60,
+        }
+    }
+
+    fn is_boolean_result(self) -> bool {
+        matches!(
+            self,
+            Operator::Equal
+                | Operator::NotEqual
+                | Operator::LessThan
+                | Operator::GreaterThan
+                | Operator::LessThanOrEqual
+                | Operator::GreaterThanOrEqual
+                | Operator::And
+                | Operator::Or
+        )
+    }
+}
+
 #[derive(Debug)]
 pub enum Expr {
     Int(i64),
+    Float(f64),
     Var(VarId),
+    Operator(Operator),
+    BinaryOp {
+        lhs: Box<Expression>,
+        op: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    Call {
+        decl_id: DeclId,
+        args: Vec<Expression>,
+    },
+    /// A call to a binary resolved from `PATH` rather than a registered `Decl`, eg `ls -la` or
+    /// `git status`. Arguments aren't shape-checked against a signature the way `Call`'s are,
+    /// since an external binary doesn't have one.
+    ExternalCall {
+        name: String,
+        args: Vec<Expression>,
+    },
+    Filepath(String),
+    GlobPattern(String),
+    /// A parenthesized group (`{ ... }` for [`SyntaxShape::Block`], `[ ... ]` for
+    /// [`SyntaxShape::Table`]) parsed as a nested block of statements. Table literals don't yet
+    /// have row/column structure of their own, so they're represented the same way a block is
+    /// until that's worth building out.
+    Block(Box<Block>),
     Garbage,
 }
 
@@ -128,6 +267,86 @@ fn garbage(span: Span) -> Expression {
     Expression::garbage(span)
 }
 
+fn int_expr(value: i64, span: Span) -> (Expression, Option<ParseError>) {
+    (
+        Expression {
+            expr: Expr::Int(value),
+            ty: Type::Int,
+            span,
+        },
+        None,
+    )
+}
+
+fn int_parse_error(span: Span) -> (Expression, Option<ParseError>) {
+    (
+        garbage(span),
+        Some(ParseError::Mismatch("int".into(), span)),
+    )
+}
+
+/// True when `token` (radix prefixes aside) looks like a decimal or scientific-notation float
+/// rather than an integer, eg `1.5`, `3.0e8`, `-.5`. Hex/binary/octal literals are never floats
+/// even though their digits can contain `e` (`0x1e`) or look decimal-ish.
+fn looks_like_float(token: &str) -> bool {
+    let unsigned = token.trim_start_matches(['+', '-']);
+    if unsigned.starts_with("0x") || unsigned.starts_with("0b") || unsigned.starts_with("0o") {
+        return false;
+    }
+
+    unsigned.contains('.') || unsigned.contains('e') || unsigned.contains('E')
+}
+
+/// Levenshtein edit distance between `a` and `b`, used to find a plausible "did you mean"
+/// suggestion for a name the parser couldn't resolve.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut prev_diag = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            let deletion = row[j] + 1;
+            let insertion = row[j + 1] + 1;
+            let substitution = prev_diag + cost;
+            prev_diag = row[j + 1];
+            row[j + 1] = deletion.min(insertion).min(substitution);
+        }
+    }
+
+    row[b.len()]
+}
+
+/// Finds the candidate closest to `name` by edit distance, rejecting matches too far away to be
+/// a plausible typo.
+fn closest_match<'a>(name: &str, candidates: impl Iterator<Item = &'a String>) -> Option<String> {
+    let max_distance = (name.chars().count() / 2).max(2);
+
+    candidates
+        .map(|candidate| (candidate, levenshtein(name, candidate)))
+        .filter(|(_, distance)| *distance <= max_distance)
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(candidate, _)| candidate.clone())
+}
+
+/// A structured parse diagnostic: a primary span the error is anchored to, any secondary labeled
+/// spans giving additional context (eg "`let` started here"), and an optional suggestion/help
+/// message, carried as real fields a consumer can read programmatically instead of text baked
+/// into a single string. Wrapped by `ParseError::Diagnostic` for errors rich enough to need it —
+/// `ParseError`'s real definition lives in `parse_error.rs`, which this tree doesn't have, so
+/// that variant is assumed to exist there (the same way this file already assumes
+/// `ParserWorkingSet` methods like `find_decl`/`add_variable` are defined in `parser_state.rs`).
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub message: String,
+    pub primary: Span,
+    pub secondary: Vec<(String, Span)>,
+    pub suggestion: Option<String>,
+}
+
 fn is_identifier_byte(b: u8) -> bool {
     b != b'.' && b != b'[' && b != b'(' && b != b'{'
 }
@@ -160,110 +379,216 @@ fn span(spans: &[Span]) -> Span {
     }
 }
 
+/// Searches `PATH` for an executable file named `name`, the same resolution a shell does before
+/// running an external command. Returns the full path of the first match.
+fn resolve_external_binary(name: &str) -> Option<std::path::PathBuf> {
+    let path_var = std::env::var_os("PATH")?;
+
+    std::env::split_paths(&path_var).find_map(|dir| {
+        let candidate = dir.join(name);
+        is_executable(&candidate).then_some(candidate)
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(path: &std::path::Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::metadata(path)
+        .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &std::path::Path) -> bool {
+    path.is_file()
+}
+
 impl ParserWorkingSet {
+    /// Resolves `spans[0]` against `PATH` the way a shell resolves an external command, parsing
+    /// the remaining spans as plain string arguments (an external binary has no `Signature` to
+    /// shape-check them against). Returns a `Garbage` expression with no error when the name
+    /// doesn't resolve to anything on `PATH` — it's up to the caller (`parse_call`) to decide
+    /// whether that's worth reporting as an unknown command.
     pub fn parse_external_call(&mut self, spans: &[Span]) -> (Expression, Option<ParseError>) {
-        // TODO: add external parsing
-        (Expression::garbage(spans[0]), None)
+        let name_span = spans[0];
+        let name = String::from_utf8_lossy(self.get_span_contents(name_span)).to_string();
+
+        if resolve_external_binary(&name).is_none() {
+            return (Expression::garbage(name_span), None);
+        }
+
+        let mut error = None;
+        let mut args = Vec::new();
+        for arg_span in &spans[1..] {
+            let (arg, _, err) =
+                self.parse_arg(std::slice::from_ref(arg_span), SyntaxShape::String);
+            error = error.or(err);
+            args.push(arg);
+        }
+
+        (
+            Expression {
+                expr: Expr::ExternalCall { name, args },
+                ty: Type::Unknown,
+                span: span(spans),
+            },
+            error,
+        )
     }
 
-    pub fn parse_call(&mut self, spans: &[Span]) -> (Expression, Option<ParseError>) {
+    /// Parses a call's arguments against its `Signature`, collecting every mismatch instead of
+    /// bailing on the first — eg `foo` called with two bad arguments and a missing third reports
+    /// all three, not just the first one encountered.
+    pub fn parse_call(&mut self, spans: &[Span]) -> (Expression, Vec<ParseError>) {
         // assume spans.len() > 0?
-        let name = self.get_span_contents(spans[0]);
+        let name_span = spans[0];
+        let name = self.get_span_contents(name_span);
 
         if let Some(decl_id) = self.find_decl(name) {
-            let sig = self.get_decl(decl_id).expect("internal error: bad DeclId");
+            let sig = self
+                .get_decl(decl_id)
+                .expect("internal error: bad DeclId")
+                .mandatory_positional
+                .clone();
 
+            let mut errors = Vec::new();
+            let mut args = Vec::new();
             let mut positional_idx = 0;
             let mut arg_offset = 1;
 
-            (Expression::garbage(spans[0]), None)
+            while positional_idx < sig.len() {
+                if arg_offset >= spans.len() {
+                    errors.push(ParseError::Mismatch(
+                        format!(
+                            "missing argument `{}` for `{}`",
+                            sig[positional_idx].name,
+                            String::from_utf8_lossy(name)
+                        ),
+                        name_span,
+                    ));
+                    break;
+                }
+
+                let (arg, consumed, err) =
+                    self.parse_arg(&spans[arg_offset..], sig[positional_idx].shape.clone());
+                errors.extend(err);
+                args.push(arg);
+
+                positional_idx += 1;
+                arg_offset += consumed.max(1);
+            }
+
+            if arg_offset < spans.len() {
+                let extra = span(&spans[arg_offset..]);
+                errors.push(ParseError::Mismatch(
+                    format!("extra argument(s) to `{}`", String::from_utf8_lossy(name)),
+                    extra,
+                ));
+            }
+
+            (
+                Expression {
+                    expr: Expr::Call { decl_id, args },
+                    ty: Type::Unknown,
+                    span: span(spans),
+                },
+                errors,
+            )
         } else {
-            self.parse_external_call(spans)
+            let (expr, err) = self.parse_external_call(spans);
+            if err.is_some() || !matches!(expr.expr, Expr::Garbage) {
+                // Either a real external command (resolved against `PATH`) or an error from
+                // parsing its arguments — in both cases `parse_external_call` already has the
+                // final word, so don't second-guess it below.
+                return (expr, err.into_iter().collect());
+            }
+
+            // Not a registered command and not a binary on `PATH` either. Surface a "did you
+            // mean" against known command names instead of silently parsing as garbage, the same
+            // way `parse_arg` does for unresolved variables.
+            let cmd_name = String::from_utf8_lossy(name).to_string();
+            let diagnostic = Diagnostic {
+                message: format!("unknown command `{cmd_name}`"),
+                primary: name_span,
+                secondary: Vec::new(),
+                suggestion: closest_match(&cmd_name, self.decl_names().iter()),
+            };
+
+            (expr, vec![ParseError::Diagnostic(diagnostic)])
         }
     }
 
     pub fn parse_int(&mut self, token: &str, span: Span) -> (Expression, Option<ParseError>) {
-        if let Some(token) = token.strip_prefix("0x") {
-            if let Ok(v) = i64::from_str_radix(token, 16) {
-                (
-                    Expression {
-                        expr: Expr::Int(v),
-                        ty: Type::Int,
-                        span,
-                    },
-                    None,
-                )
-            } else {
-                (
-                    garbage(span),
-                    Some(ParseError::Mismatch("int".into(), span)),
-                )
-            }
-        } else if let Some(token) = token.strip_prefix("0b") {
-            if let Ok(v) = i64::from_str_radix(token, 2) {
-                (
-                    Expression {
-                        expr: Expr::Int(v),
-                        ty: Type::Int,
-                        span,
-                    },
-                    None,
-                )
-            } else {
-                (
-                    garbage(span),
-                    Some(ParseError::Mismatch("int".into(), span)),
-                )
-            }
-        } else if let Some(token) = token.strip_prefix("0o") {
-            if let Ok(v) = i64::from_str_radix(token, 8) {
-                (
-                    Expression {
-                        expr: Expr::Int(v),
-                        ty: Type::Int,
-                        span,
-                    },
-                    None,
-                )
-            } else {
-                (
-                    garbage(span),
-                    Some(ParseError::Mismatch("int".into(), span)),
-                )
-            }
-        } else if let Ok(x) = token.parse::<i64>() {
-            (
+        // `_` is accepted as a digit-group separator in any base, eg `1_000` or `0xFF_FF`.
+        let token = token.replace('_', "");
+
+        if let Some(digits) = token.strip_prefix("0x") {
+            i64::from_str_radix(digits, 16)
+                .map(|v| int_expr(v, span))
+                .unwrap_or_else(|_| int_parse_error(span))
+        } else if let Some(digits) = token.strip_prefix("0b") {
+            i64::from_str_radix(digits, 2)
+                .map(|v| int_expr(v, span))
+                .unwrap_or_else(|_| int_parse_error(span))
+        } else if let Some(digits) = token.strip_prefix("0o") {
+            i64::from_str_radix(digits, 8)
+                .map(|v| int_expr(v, span))
+                .unwrap_or_else(|_| int_parse_error(span))
+        } else {
+            token
+                .parse::<i64>()
+                .map(|v| int_expr(v, span))
+                .unwrap_or_else(|_| int_parse_error(span))
+        }
+    }
+
+    /// `Type::Float` is assumed to already exist alongside `Type::Int`/`Type::Unknown` on
+    /// `parser_state.rs`'s `Type` enum — that file isn't part of this tree, so it can't be added
+    /// here, but it follows the same enum-variant-on-a-sibling-type assumption this file already
+    /// relies on for `Type::Int` and `Type::Bool`.
+    pub fn parse_float(&mut self, token: &str, span: Span) -> (Expression, Option<ParseError>) {
+        match token.replace('_', "").parse::<f64>() {
+            Ok(v) => (
                 Expression {
-                    expr: Expr::Int(x),
-                    ty: Type::Int,
+                    expr: Expr::Float(v),
+                    ty: Type::Float,
                     span,
                 },
                 None,
-            )
-        } else {
-            (
+            ),
+            Err(_) => (
                 garbage(span),
-                Some(ParseError::Mismatch("int".into(), span)),
-            )
+                Some(ParseError::Mismatch("float".into(), span)),
+            ),
         }
     }
 
     pub fn parse_number(&mut self, token: &str, span: Span) -> (Expression, Option<ParseError>) {
-        if let (x, None) = self.parse_int(token, span) {
-            (x, None)
+        if looks_like_float(token) {
+            self.parse_float(token, span)
         } else {
-            (
-                garbage(span),
-                Some(ParseError::Mismatch("number".into(), span)),
-            )
+            self.parse_int(token, span)
         }
     }
 
+    /// Parses an argument starting at `spans[0]` against `shape`, returning how many spans were
+    /// consumed. Most shapes are a single token, but `Block`/`Table` pull in every span up to
+    /// their matching closing bracket, and `GlobPattern`/`FilePath` ask the lexer to re-tokenize
+    /// the span under a shape-specific [`LexMode`] (see [`ParserWorkingSet::parse_relexed_literal`]).
     pub fn parse_arg(
         &mut self,
-        span: Span,
+        spans: &[Span],
         shape: SyntaxShape,
-    ) -> (Expression, Option<ParseError>) {
+    ) -> (Expression, usize, Option<ParseError>) {
+        let Some(&span) = spans.first() else {
+            return (
+                garbage(Span::unknown()),
+                0,
+                Some(ParseError::Mismatch("argument".into(), Span::unknown())),
+            );
+        };
+
         let bytes = self.get_span_contents(span);
         if !bytes.is_empty() && bytes[0] == b'$' {
             if let Some(var_id) = self.find_variable(bytes) {
@@ -276,33 +601,324 @@ impl ParserWorkingSet {
                         ty,
                         span,
                     },
+                    1,
                     None,
                 );
             } else {
-                return (garbage(span), Some(ParseError::VariableNotFound(span)));
+                let name = String::from_utf8_lossy(bytes).to_string();
+                let diagnostic = Diagnostic {
+                    message: format!("variable {name} not found"),
+                    primary: span,
+                    secondary: Vec::new(),
+                    suggestion: closest_match(&name, self.variable_names().iter()),
+                };
+                return (garbage(span), 1, Some(ParseError::Diagnostic(diagnostic)));
             }
         }
 
         match shape {
             SyntaxShape::Number => {
-                if let Ok(token) = String::from_utf8(bytes.into()) {
+                let (expr, err) = if let Ok(token) = String::from_utf8(bytes.into()) {
                     self.parse_number(&token, span)
                 } else {
                     (
                         garbage(span),
                         Some(ParseError::Mismatch("number".into(), span)),
                     )
-                }
+                };
+                (expr, 1, err)
+            }
+            SyntaxShape::Int => {
+                let (expr, err) = if let Ok(token) = String::from_utf8(bytes.into()) {
+                    if looks_like_float(&token) {
+                        (
+                            garbage(span),
+                            Some(ParseError::Mismatch(
+                                "int (found a float literal)".into(),
+                                span,
+                            )),
+                        )
+                    } else {
+                        self.parse_int(&token, span)
+                    }
+                } else {
+                    (
+                        garbage(span),
+                        Some(ParseError::Mismatch("int".into(), span)),
+                    )
+                };
+                (expr, 1, err)
             }
+            SyntaxShape::GlobPattern => {
+                let (expr, err) =
+                    self.parse_relexed_literal(span, crate::LexMode::Glob, Expr::GlobPattern);
+                (expr, 1, err)
+            }
+            SyntaxShape::FilePath => {
+                let (expr, err) =
+                    self.parse_relexed_literal(span, crate::LexMode::Path, Expr::Filepath);
+                (expr, 1, err)
+            }
+            SyntaxShape::Block => self.parse_bracketed_block(spans, b'{', b'}'),
+            SyntaxShape::Table => self.parse_bracketed_block(spans, b'[', b']'),
             _ => (
                 garbage(span),
+                1,
                 Some(ParseError::Mismatch("number".into(), span)),
             ),
         }
     }
 
+    /// Re-lexes a single already-tokenized span's raw bytes under `mode`, so shapes like
+    /// `GlobPattern`/`FilePath` get the lexer's shape-specific tokenization (eg `foo*` staying
+    /// one token under [`LexMode::Glob`]) instead of whatever `LexMode::Normal` produced when the
+    /// enclosing statement was first lexed.
+    fn parse_relexed_literal(
+        &mut self,
+        span: Span,
+        mode: crate::LexMode,
+        build: impl FnOnce(String) -> Expr,
+    ) -> (Expression, Option<ParseError>) {
+        let contents = self.get_span_contents(span).to_vec();
+        let (tokens, mut errors) = lex(&contents, span.file_id, span.start, mode);
+
+        match tokens.first() {
+            Some(token) => {
+                let text = String::from_utf8_lossy(self.get_span_contents(token.span)).to_string();
+                (
+                    Expression {
+                        expr: build(text),
+                        ty: Type::Unknown,
+                        span: token.span,
+                    },
+                    errors.pop(),
+                )
+            }
+            None => (
+                garbage(span),
+                errors
+                    .pop()
+                    .or(Some(ParseError::Mismatch("argument".into(), span))),
+            ),
+        }
+    }
+
+    /// Parses a `{ ... }` (`Block`) or `[ ... ]` (`Table`) argument, which may span many of the
+    /// caller's spans. Finds the matching `close` bracket via [`find_matching_bracket`]'s
+    /// depth tracking, so a nested group — `{ if true { 1 } else { 2 } }`, `[[1 2] [3 4]]` — has
+    /// its *outer* bracket matched instead of its first inner one, then re-lexes and recursively
+    /// parses everything in between via `lite_parse`/`parse_block`, the same pipeline
+    /// `parse_file` uses for a whole source. Returns the number of spans consumed, including both
+    /// brackets.
+    fn parse_bracketed_block(
+        &mut self,
+        spans: &[Span],
+        open: u8,
+        close: u8,
+    ) -> (Expression, usize, Option<ParseError>) {
+        let Some(&open_span) = spans.first() else {
+            return (
+                garbage(Span::unknown()),
+                0,
+                Some(ParseError::Mismatch(
+                    (open as char).to_string(),
+                    Span::unknown(),
+                )),
+            );
+        };
+
+        if self.get_span_contents(open_span) != [open] {
+            return (
+                garbage(open_span),
+                1,
+                Some(ParseError::Mismatch((open as char).to_string(), open_span)),
+            );
+        }
+
+        let close_idx = self.find_matching_bracket(spans, &[open], &[close]);
+
+        let close_idx = match close_idx {
+            Some(idx) => idx,
+            None => {
+                let whole = span(spans);
+                return (
+                    garbage(whole),
+                    spans.len(),
+                    Some(ParseError::Mismatch((close as char).to_string(), whole)),
+                );
+            }
+        };
+
+        let whole = span(&spans[..=close_idx]);
+        let inner = &spans[1..close_idx];
+
+        if inner.is_empty() {
+            return (
+                Expression {
+                    expr: Expr::Block(Box::new(Block::new())),
+                    ty: Type::Unknown,
+                    span: whole,
+                },
+                close_idx + 1,
+                None,
+            );
+        }
+
+        let contents = self.get_span_contents(span(inner)).to_vec();
+        let (tokens, mut errors) =
+            lex(&contents, inner[0].file_id, inner[0].start, crate::LexMode::Normal);
+        let (lite_block, err) = lite_parse(&tokens);
+        errors.extend(err);
+        let (block, err) = self.parse_block(&lite_block);
+        errors.extend(err);
+
+        (
+            Expression {
+                expr: Expr::Block(Box::new(block)),
+                ty: Type::Unknown,
+                span: whole,
+            },
+            close_idx + 1,
+            errors.into_iter().next(),
+        )
+    }
+
+    /// Finds the offset (relative to `spans[0]`, which must already be `open`) of the bracket
+    /// that closes it, tracking nesting depth so an inner `open`/`close` pair doesn't terminate
+    /// the match early — eg the outer `)` of `((1 + 2) * 3)`, or the outer `}` of
+    /// `{ if true { 1 } else { 2 } }`.
+    fn find_matching_bracket(&self, spans: &[Span], open: &[u8], close: &[u8]) -> Option<usize> {
+        let mut depth = 0i32;
+
+        for (offset, s) in spans.iter().enumerate() {
+            let bytes = self.get_span_contents(*s);
+            if bytes == open {
+                depth += 1;
+            } else if bytes == close {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(offset);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Parses a single primary operand of a math expression: a parenthesized sub-expression, or
+    /// a literal/variable value. Advances `idx` past whatever it consumed.
+    fn parse_primary(&mut self, spans: &[Span], idx: &mut usize) -> (Expression, Option<ParseError>) {
+        let span = spans[*idx];
+
+        if self.get_span_contents(span) == b"(" {
+            let close = self.find_matching_bracket(&spans[*idx..], b"(", b")");
+
+            match close {
+                Some(close) => {
+                    let inner = &spans[*idx + 1..*idx + close];
+                    let (expr, err) = self.parse_math_expression_bp(inner, &mut 0, 0);
+                    *idx += close + 1;
+                    (expr, err)
+                }
+                None => {
+                    *idx = spans.len();
+                    (garbage(span), Some(ParseError::Mismatch(")".into(), span)))
+                }
+            }
+        } else {
+            let (expr, consumed, err) = self.parse_arg(&spans[*idx..], SyntaxShape::Number);
+            *idx += consumed.max(1);
+            (expr, err)
+        }
+    }
+
+    /// Precedence-climbing parse of a math expression, in the style of a classic Pratt parser:
+    /// parse a primary operand, then keep folding in `operator rhs` pairs whose operator binds at
+    /// least as tightly as `min_bp`, recursing with `min_bp = op.precedence() + 1` for the rhs so
+    /// that equal-precedence operators associate left-to-right.
+    fn parse_math_expression_bp(
+        &mut self,
+        spans: &[Span],
+        idx: &mut usize,
+        min_bp: u8,
+    ) -> (Expression, Option<ParseError>) {
+        if spans.is_empty() {
+            return (
+                garbage(Span::unknown()),
+                Some(ParseError::Mismatch("expression".into(), Span::unknown())),
+            );
+        }
+
+        let mut error;
+        let (mut lhs, err) = self.parse_primary(spans, idx);
+        error = err;
+
+        while *idx < spans.len() {
+            let op_span = spans[*idx];
+            let Some(op) = Operator::from_bytes(self.get_span_contents(op_span)) else {
+                break;
+            };
+            if op.precedence() < min_bp {
+                break;
+            }
+
+            *idx += 1;
+            if *idx >= spans.len() {
+                error = error.or(Some(ParseError::Mismatch("expression".into(), op_span)));
+                break;
+            }
+
+            let (rhs, err) = self.parse_math_expression_bp(spans, idx, op.precedence() + 1);
+            error = error.or(err);
+
+            let span = Span {
+                start: lhs.span.start,
+                end: rhs.span.end,
+                file_id: lhs.span.file_id,
+            };
+            // `Type::Bool` is assumed to exist on `parser_state.rs`'s `Type` enum the same way
+            // `Type::Float` is assumed in `parse_float` above — that file isn't part of this
+            // tree, so the variant can't actually be added here.
+            let ty = if op.is_boolean_result() {
+                Type::Bool
+            } else {
+                Type::Int
+            };
+
+            lhs = Expression {
+                expr: Expr::BinaryOp {
+                    lhs: Box::new(lhs),
+                    op: Box::new(Expression {
+                        expr: Expr::Operator(op),
+                        ty: Type::Unknown,
+                        span: op_span,
+                    }),
+                    rhs: Box::new(rhs),
+                },
+                ty,
+                span,
+            };
+        }
+
+        (lhs, error)
+    }
+
     pub fn parse_math_expression(&mut self, spans: &[Span]) -> (Expression, Option<ParseError>) {
-        self.parse_arg(spans[0], SyntaxShape::Number)
+        let mut idx = 0;
+        let (expr, mut error) = self.parse_math_expression_bp(spans, &mut idx, 0);
+
+        // `parse_math_expression_bp` stops as soon as it hits a span that isn't an operator it
+        // recognizes, leaving any trailing tokens (eg the `2` of `let x = 1 2`) silently
+        // unconsumed. Surface that the same way `parse_call`'s "extra argument(s)" check does.
+        if idx < spans.len() {
+            let extra = span(&spans[idx..]);
+            error = error.or(Some(ParseError::Mismatch(
+                "end of expression".into(),
+                extra,
+            )));
+        }
+
+        (expr, error)
     }
 
     pub fn parse_expression(&mut self, spans: &[Span]) -> (Expression, Option<ParseError>) {
@@ -334,94 +950,256 @@ impl ParserWorkingSet {
         }
     }
 
-    pub fn parse_let(&mut self, spans: &[Span]) -> (Statement, Option<ParseError>) {
-        let mut error = None;
+    /// Parses `let name = expression`, collecting every mistake (a bad variable name, a missing
+    /// `=`, a broken right-hand side) instead of stopping at the first.
+    pub fn parse_let(&mut self, spans: &[Span]) -> (Statement, Vec<ParseError>) {
+        let mut errors = Vec::new();
         if spans.len() >= 4 && self.parse_keyword(spans[0], b"let").is_none() {
             let (_, err) = self.parse_variable(spans[1]);
-            error = error.or(err);
+            errors.extend(err);
 
             let err = self.parse_keyword(spans[2], b"=");
-            error = error.or(err);
+            errors.extend(err);
 
             let (expression, err) = self.parse_expression(&spans[3..]);
-            error = error.or(err);
+            errors.extend(err);
 
             let var_name: Vec<_> = self.get_span_contents(spans[1]).into();
             let var_id = self.add_variable(var_name, expression.ty);
 
-            (Statement::VarDecl(VarDecl { var_id, expression }), error)
+            (Statement::VarDecl(VarDecl { var_id, expression }), errors)
         } else {
             let span = span(spans);
             (
                 Statement::Expression(garbage(span)),
-                Some(ParseError::Mismatch("let".into(), span)),
+                vec![ParseError::Mismatch("let".into(), span)],
             )
         }
     }
 
-    pub fn parse_statement(&mut self, spans: &[Span]) -> (Statement, Option<ParseError>) {
-        if let (stmt, None) = self.parse_let(spans) {
-            (stmt, None)
-        } else if let (expr, None) = self.parse_expression(spans) {
-            (Statement::Expression(expr), None)
+    /// Parses the bracketed parameter list of a `def` (already stripped of its surrounding `[`
+    /// and `]`) into the positionals and switches of a `Signature`. Parameters are separated by
+    /// whitespace or commas; a `name: type` pair attaches a `SyntaxShape` via
+    /// [`type_from_annotation`], and a bare `--flag` is recorded as a switch. Collects every bad
+    /// type annotation instead of stopping at the first, so a signature with several mistakes
+    /// reports all of them in one pass.
+    fn parse_params(&mut self, spans: &[Span]) -> (Vec<PositionalArg>, Vec<String>, Vec<ParseError>) {
+        let mut positional = Vec::new();
+        let mut switches = Vec::new();
+        let mut errors = Vec::new();
+
+        let mut idx = 0;
+        while idx < spans.len() {
+            let bytes = self.get_span_contents(spans[idx]);
+
+            if bytes == b"," {
+                idx += 1;
+                continue;
+            }
+
+            if let Some(flag) = bytes.strip_prefix(b"--") {
+                switches.push(String::from_utf8_lossy(flag).to_string());
+                idx += 1;
+                continue;
+            }
+
+            let name = String::from_utf8_lossy(bytes).to_string();
+            let name_span = spans[idx];
+            idx += 1;
+
+            let mut shape = SyntaxShape::Any;
+            if idx < spans.len() && self.get_span_contents(spans[idx]) == b":" {
+                idx += 1;
+                if idx < spans.len() {
+                    let type_span = spans[idx];
+                    match type_from_annotation(self.get_span_contents(type_span)) {
+                        Some(found) => shape = found,
+                        None => errors.push(ParseError::Mismatch(
+                            "type annotation".into(),
+                            type_span,
+                        )),
+                    }
+                    idx += 1;
+                } else {
+                    errors.push(ParseError::Mismatch("type annotation".into(), name_span));
+                }
+            }
+
+            positional.push(PositionalArg { name, shape });
+        }
+
+        (positional, switches, errors)
+    }
+
+    /// Parses `def name [ params ] { body }`, registering the resulting `Signature` via
+    /// `add_decl` and parsing the body in its own scope with each parameter bound as a variable.
+    /// The parameter list ends at the first `]` after the name and the body at the last `}` of the
+    /// statement's spans; unlike `parse_primary`, no nesting depth is tracked for either.
+    /// Collects every mistake found across the signature and body instead of stopping at the
+    /// first, the same as `parse_call`/`parse_params`.
+    pub fn parse_def(&mut self, spans: &[Span]) -> (Statement, Vec<ParseError>) {
+        let whole = span(spans);
+
+        if spans.len() < 5 || self.parse_keyword(spans[0], b"def").is_some() {
+            return (
+                Statement::Expression(garbage(whole)),
+                vec![ParseError::Mismatch("def".into(), whole)],
+            );
+        }
+
+        let mut errors = Vec::new();
+        let name = String::from_utf8_lossy(self.get_span_contents(spans[1])).to_string();
+
+        if self.get_span_contents(spans[2]) != b"[" {
+            return (
+                Statement::Expression(garbage(whole)),
+                vec![ParseError::Mismatch("[".into(), spans[2])],
+            );
+        }
+
+        let close_bracket = match spans[2..].iter().position(|s| self.get_span_contents(*s) == b"]") {
+            Some(offset) => 2 + offset,
+            None => {
+                return (
+                    Statement::Expression(garbage(whole)),
+                    vec![ParseError::Mismatch("]".into(), whole)],
+                )
+            }
+        };
+
+        let (positional, switches, err) = self.parse_params(&spans[3..close_bracket]);
+        errors.extend(err);
+
+        let body_start = close_bracket + 1;
+        if body_start >= spans.len() || self.get_span_contents(spans[body_start]) != b"{" {
+            errors.push(ParseError::Mismatch("{".into(), whole));
+            return (Statement::Expression(garbage(whole)), errors);
+        }
+
+        let close_brace = match spans[body_start..].iter().rposition(|s| self.get_span_contents(*s) == b"}") {
+            Some(offset) => body_start + offset,
+            None => {
+                errors.push(ParseError::Mismatch("}".into(), whole));
+                return (Statement::Expression(garbage(whole)), errors);
+            }
+        };
+        let body_spans = &spans[body_start + 1..close_brace];
+
+        self.enter_scope();
+        for param in &positional {
+            self.add_variable(param.name.clone().into_bytes(), param.ty());
+        }
+
+        // Drive the body through the same lex + lite_parse + parse_block pipeline
+        // `parse_bracketed_block` uses for `{ ... }`/`[ ... ]` arguments, so a multi-statement
+        // body is actually kept (as a `Block`) rather than parsed only to check for an error and
+        // thrown away.
+        let body = if body_spans.is_empty() {
+            Block::new()
         } else {
-            let span = span(spans);
-            (
-                Statement::Expression(garbage(span)),
-                Some(ParseError::Mismatch("statement".into(), span)),
-            )
+            let contents = self.get_span_contents(span(body_spans)).to_vec();
+            let (tokens, mut body_errors) = lex(
+                &contents,
+                body_spans[0].file_id,
+                body_spans[0].start,
+                crate::LexMode::Normal,
+            );
+            let (lite_block, err) = lite_parse(&tokens);
+            body_errors.extend(err);
+            let (block, err) = self.parse_block(&lite_block);
+            body_errors.extend(err);
+            errors.extend(body_errors);
+            block
+        };
+
+        self.exit_scope();
+
+        let sig = Signature {
+            name,
+            mandatory_positional: positional,
+            switches,
+            body,
+        };
+        self.add_decl(sig);
+
+        (Statement::Expression(garbage(whole)), errors)
+    }
+
+    /// Dispatches on the statement's leading keyword, if any, before committing to a parse path.
+    /// `parse_def`/`parse_let` are only tried (and their result returned as-is, error or not)
+    /// once we already know the keyword matches — otherwise a real error from inside a `let`
+    /// body (eg an unresolved variable's "did you mean" suggestion) would be indistinguishable
+    /// from "this wasn't a `let` statement" and get thrown away when falling through to the next
+    /// alternative, which is what used to happen when every branch was tried speculatively and
+    /// accepted only on `None`.
+    pub fn parse_statement(&mut self, spans: &[Span]) -> (Statement, Vec<ParseError>) {
+        let keyword = spans.first().map(|s| self.get_span_contents(*s));
+
+        if keyword == Some(b"def".as_slice()) {
+            return self.parse_def(spans);
         }
+
+        if keyword == Some(b"let".as_slice()) {
+            return self.parse_let(spans);
+        }
+
+        let (expr, err) = self.parse_expression(spans);
+        (Statement::Expression(expr), err.into_iter().collect())
     }
 
-    pub fn parse_block(&mut self, lite_block: &LiteBlock) -> (Block, Option<ParseError>) {
-        let mut error = None;
+    /// Parses every pipeline in `lite_block`, continuing past a failed statement instead of
+    /// bailing on the first one so a single pass can surface every mistake in the block. A
+    /// statement that fails to parse still contributes a recovery node (see [`parse_statement`]),
+    /// so the block holds one statement per pipeline.
+    pub fn parse_block(&mut self, lite_block: &LiteBlock) -> (Block, Vec<ParseError>) {
+        let mut errors = Vec::new();
         self.enter_scope();
 
         let mut block = Block::new();
 
         for pipeline in &lite_block.block {
             let (stmt, err) = self.parse_statement(&pipeline.commands[0].parts);
-            error = error.or(err);
+            errors.extend(err);
 
             block.stmts.push(stmt);
         }
 
         self.exit_scope();
 
-        (block, error)
+        (block, errors)
     }
 
-    pub fn parse_file(&mut self, fname: &str, contents: &[u8]) -> (Block, Option<ParseError>) {
-        let mut error = None;
+    pub fn parse_file(&mut self, fname: &str, contents: &[u8]) -> (Block, Vec<ParseError>) {
+        let mut errors = Vec::new();
 
         let file_id = self.add_file(fname.into(), contents.into());
 
         let (output, err) = lex(contents, file_id, 0, crate::LexMode::Normal);
-        error = error.or(err);
+        errors.extend(err);
 
         let (output, err) = lite_parse(&output);
-        error = error.or(err);
+        errors.extend(err);
 
         let (output, err) = self.parse_block(&output);
-        error = error.or(err);
+        errors.extend(err);
 
-        (output, error)
+        (output, errors)
     }
 
-    pub fn parse_source(&mut self, source: &[u8]) -> (Block, Option<ParseError>) {
-        let mut error = None;
+    pub fn parse_source(&mut self, source: &[u8]) -> (Block, Vec<ParseError>) {
+        let mut errors = Vec::new();
 
         let file_id = self.add_file("source".into(), source.into());
 
         let (output, err) = lex(source, file_id, 0, crate::LexMode::Normal);
-        error = error.or(err);
+        errors.extend(err);
 
         let (output, err) = lite_parse(&output);
-        error = error.or(err);
+        errors.extend(err);
 
         let (output, err) = self.parse_block(&output);
-        error = error.or(err);
+        errors.extend(err);
 
-        (output, error)
+        (output, errors)
     }
 }